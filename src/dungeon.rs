@@ -1,12 +1,20 @@
 #![allow(clippy::cast_sign_loss)]
 
 use std::{
-    collections::{HashMap, HashSet},
     fmt::Display,
     ops::{Add, Mul, Sub},
 };
 
-use rand::Rng;
+use rand::{rngs::StdRng, SeedableRng};
+
+use builders::{
+    BspRoomBuilder, BuilderChain, CaveBuilder, DeadEndRemover, MazeFill, RegionConnector,
+    RoomPlacement,
+};
+use reachability::{cull_unreachable, most_distant_floor};
+
+pub mod builders;
+pub mod reachability;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Tile {
@@ -155,6 +163,18 @@ impl Mul<i32> for Vector {
     }
 }
 impl Vector {
+    pub const fn new(x: i32, y: i32) -> Self {
+        Self(x, y)
+    }
+
+    pub const fn x(self) -> i32 {
+        self.0
+    }
+
+    pub const fn y(self) -> i32 {
+        self.1
+    }
+
     pub const fn abs(self) -> i32 {
         self.0.abs() + self.1.abs()
     }
@@ -162,335 +182,212 @@ impl Vector {
 
 static CARDINALS: [Vector; 4] = [Vector(0, -1), Vector(1, 0), Vector(0, 1), Vector(-1, 0)];
 
-pub struct Dungeon<'a> {
-    n_room_tries: u32,
-    rooms: Vec<Rectangle>,
-    /// For each open position in the dungeon, the index of the connected region
-    /// that that position is a part of.
-    regions: HashMap<Vector, i32>,
-    curr_region: i32,
-    stage: &'a mut Stage,
+/// Which [`InitialMapBuilder`] lays down the base map before the shared
+/// maze-fill/connect/prune pipeline runs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InitialBuilder {
+    /// Throws rectangular rooms at the stage at random. See [`RoomPlacement`].
+    Rooms,
+    /// Lays rooms out with a binary space partition. See [`BspRoomBuilder`].
+    Bsp { min_partition_cells: i32 },
+    /// Carves organic caverns with a cellular automaton. See [`CaveBuilder`].
+    Cave {
+        fill_density: f64,
+        smoothing_iterations: u32,
+    },
 }
 
-impl<'a> Dungeon<'a> {
-    const EXTRA_CONNECTOR_CHANCE: i32 = 20;
-    const WINDING_PERCENT: i32 = 0;
-    const ROOM_EXTRA_SIZE: i32 = 0;
+/// Tuning knobs for [`Dungeon::with_config`], so callers can reshape the
+/// generated layout without forking the crate.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DungeonConfig {
+    /// Which builder lays down the base map.
+    pub initial_builder: InitialBuilder,
+    /// How many times `RoomPlacement` tries to place a room before giving up.
+    /// Higher values mean denser room coverage.
+    pub n_room_tries: u32,
+    /// Added to the random room size roll; higher values allow larger rooms.
+    pub room_extra_size: i32,
+    /// Chance (0-100) that a maze corridor turns instead of going straight
+    /// when it could keep going. `0` produces straight corridors; higher
+    /// values produce tighter, more winding catacombs.
+    pub winding_percent: i32,
+    /// 1-in-N odds of keeping a redundant connector between regions anyway,
+    /// so the dungeon isn't perfectly tree-shaped. Lower values produce
+    /// loopier, more open layouts.
+    pub extra_connector_chance: u32,
+}
 
-    pub fn new(stage: &'a mut Stage) -> Self {
+impl Default for DungeonConfig {
+    fn default() -> Self {
         Self {
+            initial_builder: InitialBuilder::Rooms,
             n_room_tries: 50,
-            rooms: Vec::new(),
-            regions: HashMap::new(),
-            curr_region: -1,
-            stage,
+            room_extra_size: 0,
+            winding_percent: 0,
+            extra_connector_chance: 20,
         }
     }
+}
 
-    pub fn generate(&mut self) {
-        assert!(
-            !(self.stage.width % 2 == 0 || self.stage.height % 2 == 0),
-            "Stage width and height must be odd"
-        );
-
-        self.regions = HashMap::new();
-
-        println!("Adding rooms");
-        self.add_rooms();
-        println!("stage: \n{}", self.stage);
-
-        // Fill in all of the empty space with mazes.
-        println!("Adding mazes");
-        for y in (1..self.stage.height).step_by(2) {
-            for x in (1..self.stage.width).step_by(2) {
-                let pos = Vector(x, y);
-                if self.get_tile(pos) != Tile::Wall {
-                    continue;
-                }
-                self.grow_maze(pos);
-            }
-        }
-        println!("stage: \n{}", self.stage);
-
-        // Connect all of the regions with mazes.
-        println!("Connecting regions");
-        self.connect_regions();
-        println!("stage: \n{}", self.stage);
+pub struct Dungeon<'a> {
+    config: DungeonConfig,
+    stage: &'a mut Stage,
+    rng: StdRng,
+    track_history: bool,
+    history: Vec<Vec<Tile>>,
+    stairs: Vector,
+}
 
-        // Remove dead ends.
-        println!("Removing dead ends");
-        self.remove_dead_ends();
-        println!("stage: \n{}", self.stage);
+impl<'a> Dungeon<'a> {
+    pub fn new(stage: &'a mut Stage) -> Self {
+        Self::with_config(stage, DungeonConfig::default())
     }
 
-    fn grow_maze(&mut self, start: Vector) {
-        let mut cells = Vec::new();
-        let mut last_dir = Vector(0, 0);
-
-        self.start_region();
-        self.carve(start, Tile::Floor);
-
-        cells.push(start);
-        while let Some(&cell) = cells.last() {
-            let mut unmade_cells = Vec::new();
-
-            for &dir in &CARDINALS {
-                if self.can_carve(cell, dir) {
-                    unmade_cells.push(dir);
-                }
-            }
-
-            if unmade_cells.is_empty() {
-                cells.pop();
-                last_dir = Vector(0, 0);
-            } else {
-                let dir = if unmade_cells.contains(&last_dir)
-                    && rand::Rng::gen_range(&mut rand::thread_rng(), 1..=100)
-                        > Self::WINDING_PERCENT
-                {
-                    last_dir
-                } else {
-                    unmade_cells[rand::random::<usize>() % unmade_cells.len()]
-                };
-
-                assert!(CARDINALS.contains(&dir));
-
-                self.carve(cell + dir, Tile::Floor);
-                self.carve(cell + dir * 2, Tile::Floor);
-
-                cells.push(cell + dir * 2);
-                last_dir = dir;
-            }
+    /// Creates a dungeon generator tuned by `config` instead of the default
+    /// knobs, e.g. tight winding catacombs (high `winding_percent`) or open
+    /// sprawling layouts (low `extra_connector_chance`).
+    pub fn with_config(stage: &'a mut Stage, config: DungeonConfig) -> Self {
+        Self {
+            config,
+            stage,
+            rng: StdRng::from_entropy(),
+            track_history: false,
+            history: Vec::new(),
+            stairs: Vector::new(0, 0),
         }
     }
 
-    fn add_rooms(&mut self) {
-        'outer: for _ in 0..self.n_room_tries {
-            // Pick a random room size. The funny math here does two things:
-            // - It makes sure rooms are odd-sized to line up with maze.
-            // - It avoids creating rooms that are too rectangular: too tall and
-            //   narrow or too wide and flat.
-            // TODO: This isn't very flexible or tunable. Do something better here.
-            let size = rand::thread_rng().gen_range(1..=3 + Self::ROOM_EXTRA_SIZE) * 2 + 1;
-            let rectangularity = rand::thread_rng().gen_range(0..=1 + (size / 2)) * 2;
-            let mut width = size;
-            let mut height = size;
-            if rand::thread_rng().gen_bool(0.5) {
-                width += rectangularity;
-            } else {
-                height += rectangularity;
-            }
-
-            let x = rand::thread_rng().gen_range(0..(self.stage.width - width) / 2) * 2 + 1;
-            let y = rand::thread_rng().gen_range(0..(self.stage.height - height) / 2) * 2 + 1;
-
-            let room = Rectangle {
-                x,
-                y,
-                w: width,
-                h: height,
-            };
-
-            for &other in &self.rooms {
-                if room.distance_to(other) <= 0 {
-                    continue 'outer;
-                }
-            }
-
-            self.rooms.push(room);
-
-            self.start_region();
-
-            for y in room.y..room.y + room.h {
-                for x in room.x..room.x + room.w {
-                    self.carve(Vector(x, y), Tile::Floor);
-                }
-            }
+    /// Creates a dungeon generator whose randomized steps are fully determined
+    /// by `seed`. Identical seeds always produce byte-identical stages, which
+    /// makes the generation reproducible for regression tests and sharing.
+    pub fn new_seeded(stage: &'a mut Stage, seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            ..Self::new(stage)
         }
     }
 
-    fn connect_regions(&mut self) {
-        // Find all of the tiles that can connect two (or more) regions.
-        let mut connector_regions = Vec::new();
-        for y in 1..self.stage.height - 1 {
-            for x in 1..self.stage.width - 1 {
-                let pos = Vector(x, y);
-                if self.get_tile(pos) != Tile::Wall {
-                    continue;
-                }
+    /// Opts into recording a snapshot of the stage after every meaningful
+    /// mutation the next `generate()` call makes, so the build can be
+    /// replayed frame-by-frame afterwards via `snapshots()`. Off by default:
+    /// runs that never call this pay no extra cost.
+    pub const fn enable_history(&mut self) {
+        self.track_history = true;
+    }
 
-                let mut regions = Vec::new();
-                for &dir in &CARDINALS {
-                    let region = self.regions.get(&(pos + dir));
-                    if let Some(&region) = region {
-                        if !regions.contains(&region) {
-                            regions.push(region);
-                        }
-                    }
-                }
+    /// The snapshots recorded by the last `generate()` call, in build order,
+    /// or an empty slice if `enable_history` was never called.
+    pub fn snapshots(&self) -> &[Vec<Tile>] {
+        &self.history
+    }
 
-                if regions.len() < 2 {
-                    continue;
-                }
+    pub fn generate(&mut self) {
+        assert!(
+            !(self.stage.width % 2 == 0 || self.stage.height % 2 == 0),
+            "Stage width and height must be odd"
+        );
 
-                connector_regions.push((pos, regions));
-            }
+        let mut chain = match self.config.initial_builder {
+            InitialBuilder::Rooms => BuilderChain::new(RoomPlacement::new(
+                self.config.n_room_tries,
+                self.config.room_extra_size,
+            )),
+            InitialBuilder::Bsp {
+                min_partition_cells,
+            } => BuilderChain::new(BspRoomBuilder::new(min_partition_cells)),
+            InitialBuilder::Cave {
+                fill_density,
+                smoothing_iterations,
+            } => BuilderChain::new(CaveBuilder::new(fill_density, smoothing_iterations)),
         }
+        .with(MazeFill::new(self.config.winding_percent))
+        .with(RegionConnector::new(self.config.extra_connector_chance))
+        .with(DeadEndRemover);
 
-        let mut connectors = connector_regions
-            .iter()
-            .map(|(pos, _)| *pos)
-            .collect::<Vec<_>>();
-
-        // Keep track of which regions have been merged. This maps an original
-        // region index to the one it has been merged to.
-        let mut merged_regions = HashMap::new();
-        let mut open_regions = HashSet::new();
-        for i in 0..=self.curr_region {
-            merged_regions.insert(i, i);
-            open_regions.insert(i);
+        if self.track_history {
+            chain = chain.with_history();
         }
 
-        // Keep connecting regions until we're down to one.
-        while open_regions.len() > 1 {
-            let connector = connectors[rand::random::<usize>() % connectors.len()];
-
-            // Carve the connection.
-            self.add_junction(connector);
-
-            // Merge the connected regions. We'll pick one region (arbitrarily) and
-            // map all of the other regions to its index.
-            let regions = connector_regions
-                .iter()
-                .find(|(pos, _)| *pos == connector)
-                .unwrap()
-                .1
-                .iter()
-                .map(|&region| merged_regions[&region])
-                .collect::<Vec<_>>();
-            let dest = *regions.first().unwrap();
-            let sources = regions.iter().skip(1).copied().collect::<Vec<_>>();
-
-            // Merge all of the affected regions. We have to look at *all* of the
-            // regions because other regions may have previously been merged with
-            // some of the ones we're merging now.
-            for i in 0..=self.curr_region {
-                if sources.contains(&merged_regions[&i]) {
-                    merged_regions.insert(i, dest);
-                }
-            }
+        let state = chain.run(self.stage, &mut self.rng);
 
-            // The sources are no longer in use.
-            for source in sources {
-                open_regions.remove(&source);
-            }
+        let start = state.rooms.first().map_or_else(
+            || first_floor(self.stage),
+            |room| Vector::new(room.x + room.w / 2, room.y + room.h / 2),
+        );
+        cull_unreachable(self.stage, start);
+        self.stairs = most_distant_floor(self.stage, start).0;
 
-            // Remove any connectors that aren't needed anymore.
-            connectors.retain(|&pos| {
-                !(|| {
-                    // Don't allow connectors right next to each other.
-                    if (connector - pos).abs() < 2 {
-                        return true;
-                    }
-
-                    // If the connector no long spans different regions, we don't need it.
-                    let regions = connector_regions
-                        .iter()
-                        .find(|(p, _)| *p == pos)
-                        .unwrap()
-                        .1
-                        .iter()
-                        .map(|&region| merged_regions[&region])
-                        .collect::<HashSet<_>>();
-
-                    if regions.len() > 1 {
-                        return false;
-                    }
-
-                    // This connector isn't needed, but connect it occasionally so that the
-                    // dungeon isn't singly-connected.
-                    if rand::thread_rng().gen_ratio(1, Self::EXTRA_CONNECTOR_CHANCE as u32) {
-                        self.add_junction(pos);
-                    }
-
-                    true
-                })()
-            });
+        self.history = state.into_history();
+        if self.track_history && self.history.last() != Some(&self.stage.tiles) {
+            self.history.push(self.stage.tiles.clone());
         }
     }
 
-    fn add_junction(&mut self, pos: Vector) {
-        if rand::thread_rng().gen_ratio(1, 4) {
-            self.set_tile(
-                pos,
-                if rand::thread_rng().gen_ratio(1, 3) {
-                    Tile::OpenDoor
-                } else {
-                    Tile::Floor
-                },
-            );
-        } else {
-            self.set_tile(pos, Tile::ClosedDoor);
-        }
+    /// The tile farthest (in steps) from the entrance, reachable from it by
+    /// construction — a good spot for the down-stairs. Only meaningful after
+    /// `generate()` has run at least once.
+    pub const fn stairs(&self) -> Vector {
+        self.stairs
     }
 
-    fn remove_dead_ends(&mut self) {
-        let mut done = false;
-
-        while !done {
-            done = true;
-
-            for y in 1..self.stage.height - 1 {
-                for x in 1..self.stage.width - 1 {
-                    let pos = Vector(x, y);
-                    if self.get_tile(pos) == Tile::Wall {
-                        continue;
-                    }
-
-                    // If it only has one exit, it's a dead end.
-                    let mut exits = 0;
-                    for &dir in &CARDINALS {
-                        let neighbor = pos + dir;
-                        if self.get_tile(neighbor) != Tile::Wall {
-                            exits += 1;
-                        }
-                    }
-
-                    if exits != 1 {
-                        continue;
-                    }
-
-                    done = false;
-                    self.set_tile(pos, Tile::Wall);
-                }
-            }
-        }
+    pub fn get_tile(&self, pos: Vector) -> Tile {
+        self.stage.get(pos).unwrap()
     }
+}
 
-    fn can_carve(&self, pos: Vector, direction: Vector) -> bool {
-        // Must end in bounds.
-        if !self.stage.contains(pos + direction * 3) {
-            return false;
+/// Falls back to scanning for any non-wall tile, for initial builders (like
+/// `CaveBuilder`) that don't record rooms.
+fn first_floor(stage: &Stage) -> Vector {
+    for y in 0..stage.height {
+        for x in 0..stage.width {
+            let pos = Vector::new(x, y);
+            if stage.get(pos) != Some(Tile::Wall) {
+                return pos;
+            }
         }
-
-        // Destination must not be open.
-        self.get_tile(pos + direction * 2) == Tile::Wall
     }
+    Vector::new(0, 0)
+}
 
-    fn start_region(&mut self) {
-        self.curr_region += 1;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_seeded_is_deterministic() {
+        // Several seeds, each regenerated several times: a single
+        // seed/repeat pair can pass by chance if some downstream step
+        // leaks HashMap iteration order into its choices, so this needs
+        // enough repeats to actually catch that class of bug.
+        for seed in 0..20 {
+            let mut first = None;
+            for _ in 0..5 {
+                let mut stage = Stage::new(41, 41);
+                Dungeon::new_seeded(&mut stage, seed).generate();
+                match &first {
+                    None => first = Some(stage.tiles),
+                    Some(first) => assert_eq!(
+                        *first, stage.tiles,
+                        "seed {seed} produced different stages across runs"
+                    ),
+                }
+            }
+        }
     }
 
-    fn carve(&mut self, pos: Vector, tile: Tile) {
-        self.set_tile(pos, tile);
-        self.regions.insert(pos, self.curr_region);
-    }
+    #[test]
+    fn history_replays_into_the_final_stage() {
+        let mut stage = Stage::new(41, 41);
+        let mut dungeon = Dungeon::new_seeded(&mut stage, 42);
+        dungeon.enable_history();
+        dungeon.generate();
 
-    pub fn get_tile(&self, pos: Vector) -> Tile {
-        self.stage.get(pos).unwrap()
-    }
+        let last_snapshot = dungeon.snapshots().last().cloned();
+        let stair_tile = dungeon.get_tile(dungeon.stairs());
 
-    fn set_tile(&mut self, pos: Vector, tile: Tile) {
-        self.stage.set(pos, tile);
+        assert!(last_snapshot.is_some());
+        assert_eq!(last_snapshot.unwrap(), stage.tiles);
+        assert_eq!(stair_tile, Tile::Floor);
     }
 }
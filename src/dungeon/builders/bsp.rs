@@ -0,0 +1,123 @@
+use rand::{rngs::StdRng, Rng};
+
+use super::{BuilderState, InitialMapBuilder};
+use crate::dungeon::{Rectangle, Stage, Tile, Vector};
+
+/// A partition of the maze grid, measured in grid cells rather than tiles:
+/// cell `(col, row)` is the tile at `(1 + 2 * col, 1 + 2 * row)`, so splitting
+/// and carving in cell units keeps every resulting room odd-aligned for free.
+struct Partition {
+    col: i32,
+    row: i32,
+    cols: i32,
+    rows: i32,
+}
+
+/// Lays rooms out with a binary space partition instead of throwing
+/// rectangles at random: the stage is recursively split in two at a random
+/// position until no partition is more than twice `min_partition_cells`
+/// across in either direction, then a room is carved inside every leaf. This covers the whole map evenly with no rejected tries.
+pub struct BspRoomBuilder {
+    min_partition_cells: i32,
+}
+
+impl BspRoomBuilder {
+    pub const fn new(min_partition_cells: i32) -> Self {
+        Self {
+            min_partition_cells,
+        }
+    }
+}
+
+impl InitialMapBuilder for BspRoomBuilder {
+    fn build(&self, stage: &mut Stage, rng: &mut StdRng, state: &mut BuilderState) {
+        let n_cols = (stage.width - 1) / 2;
+        let n_rows = (stage.height - 1) / 2;
+
+        let mut partitions = vec![Partition {
+            col: 0,
+            row: 0,
+            cols: n_cols,
+            rows: n_rows,
+        }];
+
+        while let Some(partition) = partitions.pop() {
+            let can_split_cols = partition.cols > self.min_partition_cells * 2;
+            let can_split_rows = partition.rows > self.min_partition_cells * 2;
+
+            if can_split_cols && (!can_split_rows || rng.gen_bool(0.5)) {
+                let split = rng
+                    .gen_range(self.min_partition_cells..partition.cols - self.min_partition_cells);
+                partitions.push(Partition {
+                    col: partition.col,
+                    row: partition.row,
+                    cols: split,
+                    rows: partition.rows,
+                });
+                partitions.push(Partition {
+                    col: partition.col + split,
+                    row: partition.row,
+                    cols: partition.cols - split,
+                    rows: partition.rows,
+                });
+            } else if can_split_rows {
+                let split = rng
+                    .gen_range(self.min_partition_cells..partition.rows - self.min_partition_cells);
+                partitions.push(Partition {
+                    col: partition.col,
+                    row: partition.row,
+                    cols: partition.cols,
+                    rows: split,
+                });
+                partitions.push(Partition {
+                    col: partition.col,
+                    row: partition.row + split,
+                    cols: partition.cols,
+                    rows: partition.rows - split,
+                });
+            } else {
+                carve_room(stage, rng, state, &partition);
+            }
+        }
+    }
+}
+
+fn carve_room(
+    stage: &mut Stage,
+    rng: &mut StdRng,
+    state: &mut BuilderState,
+    partition: &Partition,
+) {
+    let max_margin_cols = (partition.cols - 1) / 2;
+    let max_margin_rows = (partition.rows - 1) / 2;
+    let margin_cols = if max_margin_cols > 0 {
+        rng.gen_range(0..=max_margin_cols)
+    } else {
+        0
+    };
+    let margin_rows = if max_margin_rows > 0 {
+        rng.gen_range(0..=max_margin_rows)
+    } else {
+        0
+    };
+
+    let cols = partition.cols - margin_cols * 2;
+    let rows = partition.rows - margin_rows * 2;
+
+    let room = Rectangle {
+        x: 1 + (partition.col + margin_cols) * 2,
+        y: 1 + (partition.row + margin_rows) * 2,
+        w: cols * 2 - 1,
+        h: rows * 2 - 1,
+    };
+
+    state.rooms.push(room);
+    state.start_region();
+
+    for y in room.y..room.y + room.h {
+        for x in room.x..room.x + room.w {
+            state.carve(stage, Vector(x, y), Tile::Floor);
+        }
+    }
+    state.take_snapshot(stage);
+}
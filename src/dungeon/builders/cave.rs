@@ -0,0 +1,134 @@
+use std::collections::{HashSet, VecDeque};
+
+use rand::{rngs::StdRng, Rng};
+
+use super::{BuilderState, InitialMapBuilder};
+use crate::dungeon::{Stage, Tile, Vector, CARDINALS};
+
+/// Generates organic, non-rectangular caverns with a cellular automaton
+/// instead of rectilinear rooms and corridors: randomly fill the interior,
+/// then repeatedly smooth it with the classic 4-5 rule until it settles into
+/// natural-looking blobs.
+pub struct CaveBuilder {
+    fill_density: f64,
+    smoothing_iterations: u32,
+}
+
+impl CaveBuilder {
+    pub const fn new(fill_density: f64, smoothing_iterations: u32) -> Self {
+        Self {
+            fill_density,
+            smoothing_iterations,
+        }
+    }
+}
+
+impl Default for CaveBuilder {
+    fn default() -> Self {
+        Self::new(0.45, 4)
+    }
+}
+
+impl InitialMapBuilder for CaveBuilder {
+    fn build(&self, stage: &mut Stage, rng: &mut StdRng, state: &mut BuilderState) {
+        for y in 1..stage.height - 1 {
+            for x in 1..stage.width - 1 {
+                let tile = if rng.gen_bool(self.fill_density) {
+                    Tile::Floor
+                } else {
+                    Tile::Wall
+                };
+                stage.set(Vector(x, y), tile);
+            }
+        }
+        state.take_snapshot(stage);
+
+        for _ in 0..self.smoothing_iterations {
+            smooth(stage);
+            state.take_snapshot(stage);
+        }
+
+        flood_fill_regions(stage, state);
+    }
+}
+
+/// Runs one pass of the 4-5 rule: a cell becomes a wall if 5 or more of its
+/// eight neighbors are walls, a floor if 3 or fewer are, and is otherwise
+/// left as it was. Out-of-bounds neighbors count as walls. The next grid is
+/// always computed from a snapshot of the current one rather than mutated in
+/// place, so a cell's update this pass can't affect its neighbors' this pass.
+fn smooth(stage: &mut Stage) {
+    let (width, height) = (stage.width, stage.height);
+    let snapshot = stage.tiles.clone();
+    let at = |x: i32, y: i32| -> Tile {
+        if x < 0 || y < 0 || x >= width || y >= height {
+            Tile::Wall
+        } else {
+            snapshot[(y * width + x) as usize]
+        }
+    };
+
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let mut wall_neighbors = 0;
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    if dx == 0 && dy == 0 {
+                        continue;
+                    }
+                    if at(x + dx, y + dy) == Tile::Wall {
+                        wall_neighbors += 1;
+                    }
+                }
+            }
+
+            let next = if wall_neighbors >= 5 {
+                Tile::Wall
+            } else if wall_neighbors <= 3 {
+                Tile::Floor
+            } else {
+                at(x, y)
+            };
+            stage.set(Vector(x, y), next);
+        }
+    }
+}
+
+/// Assigns every connected blob of floor tiles its own region, mirroring
+/// what `RoomPlacement` and `MazeFill` do for their rooms and corridors, so
+/// `RegionConnector` can stitch the caves into the rest of the dungeon.
+fn flood_fill_regions(stage: &mut Stage, state: &mut BuilderState) {
+    let mut visited = HashSet::new();
+
+    for y in 0..stage.height {
+        for x in 0..stage.width {
+            let start = Vector(x, y);
+            if visited.contains(&start) || stage.get(start).unwrap() == Tile::Wall {
+                continue;
+            }
+
+            state.start_region();
+
+            let mut queue = VecDeque::new();
+            queue.push_back(start);
+            visited.insert(start);
+
+            while let Some(cell) = queue.pop_front() {
+                state.carve(stage, cell, Tile::Floor);
+
+                for &dir in &CARDINALS {
+                    let neighbor = cell + dir;
+                    if visited.contains(&neighbor)
+                        || !stage.contains(neighbor)
+                        || stage.get(neighbor).unwrap() == Tile::Wall
+                    {
+                        continue;
+                    }
+
+                    visited.insert(neighbor);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use rand::rngs::StdRng;
+
+use super::{BuilderState, MetaMapBuilder};
+use crate::dungeon::{Stage, Tile, Vector, CARDINALS};
+
+/// Fills in every corridor tile that only has a single open neighbor,
+/// repeating until none are left, so the maze has no pointless dead ends.
+pub struct DeadEndRemover;
+
+impl MetaMapBuilder for DeadEndRemover {
+    fn build(&self, stage: &mut Stage, _rng: &mut StdRng, state: &mut BuilderState) {
+        let mut done = false;
+
+        while !done {
+            done = true;
+
+            for y in 1..stage.height - 1 {
+                for x in 1..stage.width - 1 {
+                    let pos = Vector(x, y);
+                    if stage.get(pos).unwrap() == Tile::Wall {
+                        continue;
+                    }
+
+                    // If it only has one exit, it's a dead end.
+                    let mut exits = 0;
+                    for &dir in &CARDINALS {
+                        let neighbor = pos + dir;
+                        if stage.get(neighbor).unwrap() != Tile::Wall {
+                            exits += 1;
+                        }
+                    }
+
+                    if exits != 1 {
+                        continue;
+                    }
+
+                    done = false;
+                    stage.set(pos, Tile::Wall);
+                }
+            }
+
+            state.take_snapshot(stage);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use rand::{rngs::StdRng, Rng};
+
+use super::{BuilderState, MetaMapBuilder};
+use crate::dungeon::{Stage, Tile, Vector, CARDINALS};
+
+/// Fills every remaining solid odd-aligned cell with a winding maze corridor,
+/// so that rooms placed by an earlier builder end up embedded in a fully
+/// carved stage.
+pub struct MazeFill {
+    /// Chance (0-100) that a corridor turns instead of going straight when
+    /// it could keep going. `0` produces straight corridors; higher values
+    /// produce more winding ones.
+    winding_percent: i32,
+}
+
+impl MazeFill {
+    pub const fn new(winding_percent: i32) -> Self {
+        Self { winding_percent }
+    }
+
+    fn grow_maze(
+        &self,
+        stage: &mut Stage,
+        rng: &mut StdRng,
+        state: &mut BuilderState,
+        start: Vector,
+    ) {
+        let mut cells = Vec::new();
+        let mut last_dir = Vector(0, 0);
+
+        state.start_region();
+        state.carve(stage, start, Tile::Floor);
+
+        cells.push(start);
+        while let Some(&cell) = cells.last() {
+            let mut unmade_cells = Vec::new();
+
+            for &dir in &CARDINALS {
+                if can_carve(stage, cell, dir) {
+                    unmade_cells.push(dir);
+                }
+            }
+
+            if unmade_cells.is_empty() {
+                cells.pop();
+                last_dir = Vector(0, 0);
+            } else {
+                let dir = if unmade_cells.contains(&last_dir)
+                    && rng.gen_range(1..=100) > self.winding_percent
+                {
+                    last_dir
+                } else {
+                    unmade_cells[rng.gen_range(0..unmade_cells.len())]
+                };
+
+                assert!(CARDINALS.contains(&dir));
+
+                state.carve(stage, cell + dir, Tile::Floor);
+                state.carve(stage, cell + dir * 2, Tile::Floor);
+
+                cells.push(cell + dir * 2);
+                last_dir = dir;
+            }
+
+            state.take_snapshot(stage);
+        }
+    }
+}
+
+impl MetaMapBuilder for MazeFill {
+    fn build(&self, stage: &mut Stage, rng: &mut StdRng, state: &mut BuilderState) {
+        for y in (1..stage.height).step_by(2) {
+            for x in (1..stage.width).step_by(2) {
+                let pos = Vector(x, y);
+                if stage.get(pos).unwrap() != Tile::Wall {
+                    continue;
+                }
+                self.grow_maze(stage, rng, state, pos);
+            }
+        }
+    }
+}
+
+fn can_carve(stage: &Stage, pos: Vector, direction: Vector) -> bool {
+    // Must end in bounds.
+    if !stage.contains(pos + direction * 3) {
+        return false;
+    }
+
+    // Destination must not be open.
+    stage.get(pos + direction * 2).unwrap() == Tile::Wall
+}
@@ -0,0 +1,210 @@
+//! A chainable pipeline of map builders, in the spirit of the layered
+//! builder-chaining pattern used by other roguelike map generators: an
+//! [`InitialMapBuilder`] lays down a base map from nothing, then zero or
+//! more [`MetaMapBuilder`]s mutate it in sequence. A [`BuilderChain`] owns
+//! the sequence and the [`BuilderState`] that the builders share as they run.
+//!
+//! `stage` and `state` are used throughout as the names for, respectively,
+//! the map being built and the shared [`BuilderState`]; clippy's
+//! `similar_names` lint flags that pairing everywhere it occurs.
+#![allow(clippy::similar_names)]
+
+use rand::rngs::StdRng;
+
+use super::{Rectangle, Stage, Tile, Vector};
+use std::collections::HashMap;
+
+mod bsp;
+mod cave;
+mod dead_end_remover;
+mod maze_fill;
+mod region_connector;
+mod room_placement;
+
+pub use bsp::BspRoomBuilder;
+pub use cave::CaveBuilder;
+pub use dead_end_remover::DeadEndRemover;
+pub use maze_fill::MazeFill;
+pub use region_connector::RegionConnector;
+pub use room_placement::RoomPlacement;
+
+/// State shared between the builders in a [`BuilderChain`] as the stage is
+/// assembled: the rooms carved so far and the region each open tile belongs
+/// to.
+pub struct BuilderState {
+    pub rooms: Vec<Rectangle>,
+    pub regions: HashMap<Vector, i32>,
+    pub curr_region: i32,
+    history: Option<Vec<Vec<Tile>>>,
+}
+
+impl BuilderState {
+    fn new(record_history: bool) -> Self {
+        Self {
+            rooms: Vec::new(),
+            regions: HashMap::new(),
+            curr_region: -1,
+            history: record_history.then(Vec::new),
+        }
+    }
+
+    const fn start_region(&mut self) {
+        self.curr_region += 1;
+    }
+
+    fn carve(&mut self, stage: &mut Stage, pos: Vector, tile: Tile) {
+        stage.set(pos, tile);
+        self.regions.insert(pos, self.curr_region);
+    }
+
+    /// Records a copy of `stage`'s tiles, if history tracking was enabled via
+    /// [`BuilderChain::with_history`]. A no-op otherwise, so chains that
+    /// don't ask for history pay nothing for it.
+    pub fn take_snapshot(&mut self, stage: &Stage) {
+        if let Some(history) = &mut self.history {
+            history.push(stage.tiles.clone());
+        }
+    }
+
+    /// Consumes the recorded history, or an empty `Vec` if it was never
+    /// enabled.
+    pub fn into_history(self) -> Vec<Vec<Tile>> {
+        self.history.unwrap_or_default()
+    }
+}
+
+/// Produces a base map from scratch, ignoring whatever was already in
+/// `stage`. Every [`BuilderChain`] must start with exactly one of these.
+pub trait InitialMapBuilder {
+    fn build(&self, stage: &mut Stage, rng: &mut StdRng, state: &mut BuilderState);
+}
+
+/// Mutates a map that an [`InitialMapBuilder`] (or an earlier
+/// `MetaMapBuilder`) has already produced.
+pub trait MetaMapBuilder {
+    fn build(&self, stage: &mut Stage, rng: &mut StdRng, state: &mut BuilderState);
+}
+
+/// Runs an [`InitialMapBuilder`] followed by a sequence of [`MetaMapBuilder`]s
+/// against a stage, threading a single [`BuilderState`] through all of them.
+pub struct BuilderChain {
+    initial: Box<dyn InitialMapBuilder>,
+    meta: Vec<Box<dyn MetaMapBuilder>>,
+    record_history: bool,
+}
+
+impl BuilderChain {
+    pub fn new(initial: impl InitialMapBuilder + 'static) -> Self {
+        Self {
+            initial: Box::new(initial),
+            meta: Vec::new(),
+            record_history: false,
+        }
+    }
+
+    #[must_use]
+    pub fn with(mut self, builder: impl MetaMapBuilder + 'static) -> Self {
+        self.meta.push(Box::new(builder));
+        self
+    }
+
+    /// Opts into recording a snapshot of the stage after every meaningful
+    /// mutation the chain makes, so the run can be replayed frame-by-frame
+    /// afterwards via [`BuilderState::into_history`]. Off by default.
+    #[must_use]
+    pub const fn with_history(mut self) -> Self {
+        self.record_history = true;
+        self
+    }
+
+    pub fn run(&self, stage: &mut Stage, rng: &mut StdRng) -> BuilderState {
+        let mut state = BuilderState::new(self.record_history);
+        self.initial.build(stage, rng, &mut state);
+        for builder in &self.meta {
+            builder.build(stage, rng, &mut state);
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::dungeon::reachability::{cull_unreachable, distances_from};
+
+    /// Cave chains carve no rooms, so (unlike the BSP test) there's no
+    /// `state.rooms` entry to start the search from; scan for the first
+    /// floor tile instead.
+    fn first_floor(stage: &Stage) -> Vector {
+        for y in 0..stage.height {
+            for x in 0..stage.width {
+                let pos = Vector::new(x, y);
+                if stage.get(pos) != Some(Tile::Wall) {
+                    return pos;
+                }
+            }
+        }
+        panic!("cave chain carved no floor tiles");
+    }
+
+    #[test]
+    fn bsp_chain_is_fully_connected() {
+        let mut stage = Stage::new(41, 41);
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let chain = BuilderChain::new(BspRoomBuilder::new(3))
+            .with(MazeFill::new(0))
+            .with(RegionConnector::new(20))
+            .with(DeadEndRemover);
+        let state = chain.run(&mut stage, &mut rng);
+
+        let room = state.rooms.first().expect("BspRoomBuilder always carves a room");
+        let start = Vector::new(room.x + room.w / 2, room.y + room.h / 2);
+        let distances = distances_from(&stage, start);
+        for y in 0..stage.height {
+            for x in 0..stage.width {
+                let pos = Vector::new(x, y);
+                if stage.get(pos) != Some(Tile::Wall) {
+                    assert!(distances.contains_key(&pos), "{pos:?} is unreachable from {start:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cave_chain_is_fully_connected() {
+        // Cave regions can be fully walled off from the rest of the map, so
+        // `RegionConnector` may legitimately leave some unconnected (rather
+        // than panicking, as it used to). Run across many seeds to make sure
+        // that's handled cleanly, then cull what's left unreachable, exactly
+        // as `Dungeon::generate` does, and check the result is connected.
+        for seed in 0..50 {
+            let mut stage = Stage::new(41, 41);
+            let mut rng = StdRng::seed_from_u64(seed);
+
+            let chain = BuilderChain::new(CaveBuilder::new(0.45, 4))
+                .with(MazeFill::new(0))
+                .with(RegionConnector::new(20))
+                .with(DeadEndRemover);
+            chain.run(&mut stage, &mut rng);
+
+            let start = first_floor(&stage);
+            cull_unreachable(&mut stage, start);
+
+            let distances = distances_from(&stage, start);
+            for y in 0..stage.height {
+                for x in 0..stage.width {
+                    let pos = Vector::new(x, y);
+                    if stage.get(pos) != Some(Tile::Wall) {
+                        assert!(
+                            distances.contains_key(&pos),
+                            "{pos:?} is unreachable from {start:?} (seed {seed})"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
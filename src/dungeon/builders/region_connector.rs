@@ -0,0 +1,152 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::{rngs::StdRng, Rng};
+
+use super::{BuilderState, MetaMapBuilder};
+use crate::dungeon::{Stage, Tile, Vector, CARDINALS};
+
+/// Connects every region produced by earlier builders into one, carving a
+/// door or open passage at a randomly chosen connector tile between each
+/// pair, and occasionally adding extra connections so the result isn't
+/// perfectly tree-shaped. A region with no connector tile to any other
+/// region is left unconnected.
+pub struct RegionConnector {
+    /// 1-in-`extra_connector_chance` odds of keeping a redundant connector
+    /// anyway, so the dungeon isn't perfectly tree-shaped. Lower values
+    /// produce loopier, more open layouts.
+    extra_connector_chance: u32,
+}
+
+impl RegionConnector {
+    pub const fn new(extra_connector_chance: u32) -> Self {
+        Self {
+            extra_connector_chance,
+        }
+    }
+}
+
+impl MetaMapBuilder for RegionConnector {
+    fn build(&self, stage: &mut Stage, rng: &mut StdRng, state: &mut BuilderState) {
+        // Find all of the tiles that can connect two (or more) regions,
+        // indexed by position so later lookups don't have to linear-scan.
+        // `connectors` tracks the same positions in scan order: a `HashMap`'s
+        // iteration order is randomized per-process, so picking from its
+        // keys directly would make `gen_range` below non-deterministic even
+        // for a fixed seed.
+        let mut connector_regions = HashMap::new();
+        let mut connectors = Vec::new();
+        for y in 1..stage.height - 1 {
+            for x in 1..stage.width - 1 {
+                let pos = Vector(x, y);
+                if stage.get(pos).unwrap() != Tile::Wall {
+                    continue;
+                }
+
+                let mut regions = Vec::new();
+                for &dir in &CARDINALS {
+                    let region = state.regions.get(&(pos + dir));
+                    if let Some(&region) = region {
+                        if !regions.contains(&region) {
+                            regions.push(region);
+                        }
+                    }
+                }
+
+                if regions.len() < 2 {
+                    continue;
+                }
+
+                connectors.push(pos);
+                connector_regions.insert(pos, regions);
+            }
+        }
+
+        // Keep track of which regions have been merged. This maps an original
+        // region index to the one it has been merged to.
+        let mut merged_regions = HashMap::new();
+        let mut open_regions = HashSet::new();
+        for i in 0..=state.curr_region {
+            merged_regions.insert(i, i);
+            open_regions.insert(i);
+        }
+
+        // Keep connecting regions until we're down to one. A region with no
+        // connector to anywhere else (e.g. a cave pocket fully walled in by
+        // its neighbours) can leave `connectors` empty before every region
+        // is merged; leave it unconnected rather than panicking, since
+        // `Dungeon::generate`'s reachability cull already discards it.
+        while open_regions.len() > 1 && !connectors.is_empty() {
+            let connector = connectors[rng.gen_range(0..connectors.len())];
+
+            // Carve the connection.
+            add_junction(stage, rng, state, connector);
+
+            // Merge the connected regions. We'll pick one region (arbitrarily) and
+            // map all of the other regions to its index.
+            let regions = connector_regions[&connector]
+                .iter()
+                .map(|&region| merged_regions[&region])
+                .collect::<Vec<_>>();
+            let dest = *regions.first().unwrap();
+            let sources = regions.iter().skip(1).copied().collect::<Vec<_>>();
+
+            // Merge all of the affected regions. We have to look at *all* of the
+            // regions because other regions may have previously been merged with
+            // some of the ones we're merging now.
+            for i in 0..=state.curr_region {
+                if sources.contains(&merged_regions[&i]) {
+                    merged_regions.insert(i, dest);
+                }
+            }
+
+            // The sources are no longer in use.
+            for source in sources {
+                open_regions.remove(&source);
+            }
+
+            // Remove any connectors that aren't needed anymore.
+            connectors.retain(|&pos| {
+                !(|| {
+                    // Don't allow connectors right next to each other.
+                    if (connector - pos).abs() < 2 {
+                        return true;
+                    }
+
+                    // If the connector no long spans different regions, we don't need it.
+                    let regions = connector_regions[&pos]
+                        .iter()
+                        .map(|&region| merged_regions[&region])
+                        .collect::<HashSet<_>>();
+
+                    if regions.len() > 1 {
+                        return false;
+                    }
+
+                    // This connector isn't needed, but connect it occasionally so that the
+                    // dungeon isn't singly-connected.
+                    if rng.gen_ratio(1, self.extra_connector_chance) {
+                        add_junction(stage, rng, state, pos);
+                    }
+
+                    true
+                })()
+            });
+        }
+    }
+}
+
+fn add_junction(stage: &mut Stage, rng: &mut StdRng, state: &mut BuilderState, pos: Vector) {
+    if rng.gen_ratio(1, 4) {
+        stage.set(
+            pos,
+            if rng.gen_ratio(1, 3) {
+                Tile::OpenDoor
+            } else {
+                Tile::Floor
+            },
+        );
+    } else {
+        stage.set(pos, Tile::ClosedDoor);
+    }
+    state.take_snapshot(stage);
+}
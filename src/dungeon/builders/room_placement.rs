@@ -0,0 +1,68 @@
+use rand::{rngs::StdRng, Rng};
+
+use super::{BuilderState, InitialMapBuilder};
+use crate::dungeon::{Rectangle, Stage, Tile, Vector};
+
+/// Throws rectangular rooms at the stage at random, rejecting any that
+/// overlap an already-placed room, until it has made `n_tries` attempts.
+pub struct RoomPlacement {
+    n_tries: u32,
+    /// Added to the random room size roll; higher values allow larger rooms.
+    room_extra_size: i32,
+}
+
+impl RoomPlacement {
+    pub const fn new(n_tries: u32, room_extra_size: i32) -> Self {
+        Self {
+            n_tries,
+            room_extra_size,
+        }
+    }
+}
+
+impl InitialMapBuilder for RoomPlacement {
+    fn build(&self, stage: &mut Stage, rng: &mut StdRng, state: &mut BuilderState) {
+        'outer: for _ in 0..self.n_tries {
+            // Pick a random room size. The funny math here does two things:
+            // - It makes sure rooms are odd-sized to line up with maze.
+            // - It avoids creating rooms that are too rectangular: too tall and
+            //   narrow or too wide and flat.
+            let size = rng.gen_range(1..=3 + self.room_extra_size) * 2 + 1;
+            let rectangularity = rng.gen_range(0..=1 + (size / 2)) * 2;
+            let mut width = size;
+            let mut height = size;
+            if rng.gen_bool(0.5) {
+                width += rectangularity;
+            } else {
+                height += rectangularity;
+            }
+
+            let x = rng.gen_range(0..(stage.width - width) / 2) * 2 + 1;
+            let y = rng.gen_range(0..(stage.height - height) / 2) * 2 + 1;
+
+            let room = Rectangle {
+                x,
+                y,
+                w: width,
+                h: height,
+            };
+
+            for &other in &state.rooms {
+                if room.distance_to(other) <= 0 {
+                    continue 'outer;
+                }
+            }
+
+            state.rooms.push(room);
+
+            state.start_region();
+
+            for y in room.y..room.y + room.h {
+                for x in room.x..room.x + room.w {
+                    state.carve(stage, Vector(x, y), Tile::Floor);
+                }
+            }
+            state.take_snapshot(stage);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Connectivity analysis over an already-generated [`Stage`], for placing
+//! objectives and guaranteeing the dungeon is fully navigable.
+
+use std::collections::{HashMap, VecDeque};
+
+use super::{Stage, Tile, Vector, CARDINALS};
+
+/// Runs a breadth-first Dijkstra (every step costs the same, so this is
+/// equivalent to a plain BFS) over every non-wall tile reachable from
+/// `start`, recording each tile's distance in tile-steps.
+pub fn distances_from(stage: &Stage, start: Vector) -> HashMap<Vector, i32> {
+    let mut distances = HashMap::new();
+    distances.insert(start, 0);
+
+    let mut frontier = VecDeque::new();
+    frontier.push_back(start);
+
+    while let Some(pos) = frontier.pop_front() {
+        let dist = distances[&pos];
+        for &dir in &CARDINALS {
+            let neighbor = pos + dir;
+            if distances.contains_key(&neighbor) || stage.get(neighbor) == Some(Tile::Wall) {
+                continue;
+            }
+
+            distances.insert(neighbor, dist + 1);
+            frontier.push_back(neighbor);
+        }
+    }
+
+    distances
+}
+
+/// Returns the reachable tile farthest from `start`, along with its
+/// distance in tile-steps — ideal for placing the down-stairs as far from
+/// the up-stairs as the layout allows. Ties are broken on position so the
+/// choice is deterministic regardless of `HashMap` iteration order.
+pub fn most_distant_floor(stage: &Stage, start: Vector) -> (Vector, i32) {
+    distances_from(stage, start)
+        .into_iter()
+        .max_by_key(|&(pos, dist)| (dist, pos.y(), pos.x()))
+        .expect("start is always reachable from itself")
+}
+
+/// Turns every open tile that isn't reachable from `start` back into a
+/// wall, guaranteeing the whole stage is connected and navigable from
+/// `start`.
+pub fn cull_unreachable(stage: &mut Stage, start: Vector) {
+    let distances = distances_from(stage, start);
+
+    for y in 0..stage.height {
+        for x in 0..stage.width {
+            let pos = Vector(x, y);
+            if stage.get(pos) != Some(Tile::Wall) && !distances.contains_key(&pos) {
+                stage.set(pos, Tile::Wall);
+            }
+        }
+    }
+}
@@ -2,15 +2,42 @@
 
 use dungeon::Stage;
 
-use crate::dungeon::Dungeon;
+use crate::dungeon::{Dungeon, DungeonConfig, InitialBuilder};
 
 mod dungeon;
 
-fn main() {
+fn generate_and_print(config: DungeonConfig) {
     let mut stage = Stage::new(301, 71);
+    let mut generator = Dungeon::with_config(&mut stage, config);
+    generator.generate();
+    let stairs = generator.stairs();
     println!("{stage}");
-    println!();
-    let mut dungeon_generator = Dungeon::new(&mut stage);
-    dungeon_generator.generate();
-    // println!("{stage}");
+    println!("stairs at ({}, {})", stairs.x(), stairs.y());
+}
+
+fn main() {
+    generate_and_print(DungeonConfig::default());
+
+    generate_and_print(DungeonConfig {
+        initial_builder: InitialBuilder::Bsp {
+            min_partition_cells: 3,
+        },
+        ..DungeonConfig::default()
+    });
+
+    generate_and_print(DungeonConfig {
+        initial_builder: InitialBuilder::Cave {
+            fill_density: 0.45,
+            smoothing_iterations: 4,
+        },
+        ..DungeonConfig::default()
+    });
+
+    let mut seeded_stage = Stage::new(81, 41);
+    let mut seeded_generator = Dungeon::new_seeded(&mut seeded_stage, 42);
+    seeded_generator.enable_history();
+    seeded_generator.generate();
+    println!("frames recorded: {}", seeded_generator.snapshots().len());
+    let stairs = seeded_generator.stairs();
+    println!("tile at stairs: {:?}", seeded_generator.get_tile(stairs));
 }